@@ -6,8 +6,8 @@ fn main() {
         Ok(uri) => {
             println!("The URI: {}", uri);
             match sectok::decode(&uri) {
-                Some(token) => println!("The decoded token: {}", token),
-                None => println!("The URI is invalid, cannot decode the token"),
+                Ok(token) => println!("The decoded token: {}", token),
+                Err(e) => println!("The URI is invalid, cannot decode the token: {}", e),
             }
         }
         Err(e) => {
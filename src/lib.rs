@@ -1,12 +1,21 @@
 //! A Rust library to interact with [RFC 8959](https://tools.ietf.org/html/rfc8959) secret-token URIs.
 //!
 //! See the RFC text for motivation and details.
-#![feature(test)]
+//!
+//! The `std` feature is enabled by default and pulls in the `#[bench]` harness. Disable it
+//! (`--no-default-features`) to use `encode`/`decode` in a `no_std` + `alloc` context. The
+//! optional `simd` feature speeds up validation of long tokens by classifying several bytes
+//! at once instead of one at a time; it has no effect on behavior.
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "std", feature(test))]
+extern crate alloc;
+#[cfg(feature = "std")]
 extern crate test;
-#[macro_use]
-extern crate lazy_static;
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
 use percent_encoding::{percent_decode, percent_encode, AsciiSet, NON_ALPHANUMERIC};
-use regex::bytes::Regex;
 
 /// The URI scheme used.
 pub const SCHEME: &'static str = "secret-token";
@@ -35,6 +44,51 @@ const DISALLOWED_CHARACTERS: &AsciiSet = &NON_ALPHANUMERIC
     .remove(b':')
     .remove(b'@');
 
+/// The reason [decode](fn.decode) failed to turn a URI into a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecodeError {
+    /// The URI does not start with the [PREFIX](const.PREFIX).
+    MissingPrefix,
+    /// The URI has the [PREFIX](const.PREFIX) but nothing after it.
+    EmptyToken,
+    /// The token contains a `%` that is not followed by two hex digits.
+    InvalidPercentEncoding,
+    /// The token, once percent-decoded, is not valid UTF-8.
+    InvalidUtf8,
+    /// The token contains a byte that is neither unreserved nor part of a
+    /// valid percent-encoded triplet.
+    DisallowedCharacter {
+        /// The offending byte.
+        byte: u8,
+        /// Its position within the token, not within the whole URI.
+        position: usize,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::MissingPrefix => {
+                write!(f, "URI does not start with the {:?} prefix", PREFIX)
+            }
+            DecodeError::EmptyToken => write!(f, "URI has no token after the prefix"),
+            DecodeError::InvalidPercentEncoding => {
+                write!(f, "token contains a '%' not followed by two hex digits")
+            }
+            DecodeError::InvalidUtf8 => write!(f, "token is not valid UTF-8 once decoded"),
+            DecodeError::DisallowedCharacter { byte, position } => write!(
+                f,
+                "token contains disallowed byte {:#04x} at position {}",
+                byte, position
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
 /// Encodes the secret into the secret-token URI.
 ///
 /// Non-ascii characters are UTF-8-encoded, disallowed characters then are percent-encoded,
@@ -49,41 +103,409 @@ pub fn encode(secret: &str) -> String {
 
 /// Decodes the secret-token URI into a secret.
 ///
-/// This function returns `None` when `uri`:
+/// # Errors
+///
+/// Returns a [DecodeError](enum.DecodeError) when `uri`:
 ///
-/// * Does not start with the [PREFIX](const.PREFIX)
-/// * Has no token
-/// * Has token that contains invalid percent-encoded UTF-8
-pub fn decode(uri: impl AsRef<[u8]>) -> Option<String> {
+/// * Does not start with the [PREFIX](const.PREFIX) ([MissingPrefix](enum.DecodeError#variant.MissingPrefix))
+/// * Has no token ([EmptyToken](enum.DecodeError#variant.EmptyToken))
+/// * Has a token with a `%` not followed by two hex digits
+///   ([InvalidPercentEncoding](enum.DecodeError#variant.InvalidPercentEncoding))
+/// * Has a token with a byte that isn't allowed
+///   ([DisallowedCharacter](enum.DecodeError#variant.DisallowedCharacter))
+/// * Has a token that, once percent-decoded, isn't valid UTF-8
+///   ([InvalidUtf8](enum.DecodeError#variant.InvalidUtf8))
+pub fn decode(uri: impl AsRef<[u8]>) -> Result<String, DecodeError> {
     let uri = uri.as_ref();
     if !uri.starts_with(PREFIX.as_bytes()) {
-        return None;
-    }
-    lazy_static! {
-        static ref ALLOWED_CHARACTERS_RE: Regex =
-            Regex::new(r"^([a-zA-Z0-9._~!$&'()*+,;=:@-]|%[a-fA-F0-9]{2})*$").unwrap();
-    }
-    let uri = &uri[PREFIX.as_bytes().len()..];
-    match uri {
-        b"" => None,
-        rest => match percent_decode(&rest).decode_utf8() {
-            Ok(decoded) => {
-                if ALLOWED_CHARACTERS_RE.is_match(rest) {
-                    Some(decoded.into_owned())
-                } else {
-                    None
+        return Err(DecodeError::MissingPrefix);
+    }
+    let rest = &uri[PREFIX.as_bytes().len()..];
+    validate_token(rest)?;
+    // `validate_token` only accepts bytes that are either unreserved ASCII or part of a
+    // percent-encoded triplet, so `rest` is guaranteed to be ASCII at this point.
+    let rest = core::str::from_utf8(rest).expect("validated token is ASCII");
+    decode_validated_token(rest).map(Cow::into_owned)
+}
+
+/// Decodes the secret-token URI into a secret, borrowing from `uri` when it contains no
+/// percent-encoding rather than always allocating a new [String].
+///
+/// See [decode](fn.decode) for the error conditions; they are identical.
+pub fn decode_cow(uri: &str) -> Result<Cow<'_, str>, DecodeError> {
+    if !uri.as_bytes().starts_with(PREFIX.as_bytes()) {
+        return Err(DecodeError::MissingPrefix);
+    }
+    let rest = &uri[PREFIX.len()..];
+    validate_token(rest.as_bytes())?;
+    decode_validated_token(rest)
+}
+
+/// Decodes the secret-token URI into a [SecretToken], for use where the presented value is
+/// about to be checked against an expected one.
+///
+/// See [decode](fn.decode) for the error conditions; they are identical.
+pub fn decode_secret(uri: impl AsRef<[u8]>) -> Result<SecretToken, DecodeError> {
+    decode(uri).map(SecretToken::new)
+}
+
+/// A decoded secret, meant to be held onto and compared against presented credentials.
+///
+/// Unlike a plain [String], it is safe to use directly in an authentication check:
+///
+/// * [Debug](fmt::Debug) and [Display](fmt::Display) never print the secret, only
+///   `secret-token:***`.
+/// * [PartialEq] compares the full length of both sides regardless of where they first
+///   differ, so the time it takes doesn't leak how much of a guess was correct.
+/// * Its backing buffer is overwritten with zeros before being deallocated.
+pub struct SecretToken {
+    token: String,
+}
+
+impl SecretToken {
+    fn new(token: String) -> Self {
+        SecretToken { token }
+    }
+}
+
+impl fmt::Debug for SecretToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}***", PREFIX)
+    }
+}
+
+impl fmt::Display for SecretToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}***", PREFIX)
+    }
+}
+
+impl PartialEq for SecretToken {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (self.token.as_bytes(), other.token.as_bytes());
+        if a.len() != b.len() {
+            return false;
+        }
+        let mut difference = 0u8;
+        for (x, y) in a.iter().zip(b.iter()) {
+            difference |= x ^ y;
+        }
+        difference == 0
+    }
+}
+
+impl Eq for SecretToken {}
+
+impl Drop for SecretToken {
+    fn drop(&mut self) {
+        // SAFETY: overwriting every byte with 0 (valid UTF-8 on its own) keeps `self.token`
+        // the same length and still valid UTF-8, so the `String`'s invariants hold throughout.
+        unsafe {
+            for byte in self.token.as_bytes_mut() {
+                core::ptr::write_volatile(byte, 0);
+            }
+        }
+    }
+}
+
+fn validate_token(rest: &[u8]) -> Result<(), DecodeError> {
+    if rest.is_empty() {
+        return Err(DecodeError::EmptyToken);
+    }
+    check_allowed_characters(rest)
+}
+
+/// Percent-decodes an already-[validate_token]d token, borrowing it unchanged when it contains
+/// no `%` byte.
+fn decode_validated_token(token: &str) -> Result<Cow<'_, str>, DecodeError> {
+    if !token.contains('%') {
+        return Ok(Cow::Borrowed(token));
+    }
+    match percent_decode(token.as_bytes()).decode_utf8() {
+        Ok(decoded) => Ok(Cow::Owned(decoded.into_owned())),
+        Err(_) => Err(DecodeError::InvalidUtf8),
+    }
+}
+
+/// Returns `true` for the bytes RFC 8959's token grammar allows unescaped.
+const fn is_allowed_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric()
+        || matches!(
+            byte,
+            b'-' | b'.'
+                | b'_'
+                | b'~'
+                | b'!'
+                | b'$'
+                | b'&'
+                | b'\''
+                | b'('
+                | b')'
+                | b'*'
+                | b'+'
+                | b','
+                | b';'
+                | b'='
+                | b':'
+                | b'@'
+        )
+}
+
+const fn build_allowed_characters_table() -> [bool; 256] {
+    let mut table = [false; 256];
+    let mut byte = 0usize;
+    while byte < 256 {
+        table[byte] = is_allowed_byte(byte as u8);
+        byte += 1;
+    }
+    table
+}
+
+/// `ALLOWED_CHARACTERS_TABLE[byte as usize]` is `true` for the bytes RFC 8959's token grammar
+/// allows unescaped. Used instead of a regex so the check stays a plain array lookup and works
+/// under `no_std`.
+const ALLOWED_CHARACTERS_TABLE: [bool; 256] = build_allowed_characters_table();
+
+/// Optional SIMD fast path for [check_allowed_characters], enabled via the `simd` feature.
+///
+/// Real tokens are usually long runs of unreserved ASCII with no percent-encoding at all
+/// (JWTs, base64url blobs, UUIDs), so the only question worth answering in bulk is "how many
+/// leading bytes of `token` are both allowed and `%`-free?". Whatever it can't prove clean it
+/// leaves for the scalar scan in [check_allowed_characters] to pick up from there, so the two
+/// always agree on the result.
+#[cfg(feature = "simd")]
+mod simd {
+    /// Returns the length of the longest prefix of `token` made up of bytes that are all
+    /// allowed and contain no `%`.
+    pub(crate) fn clean_prefix_len(token: &[u8]) -> usize {
+        #[cfg(all(target_arch = "x86_64", feature = "std"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return unsafe { x86_64::clean_prefix_len_avx2(token) };
+            }
+            if is_x86_feature_detected!("sse4.2") {
+                return unsafe { x86_64::clean_prefix_len_sse42(token) };
+            }
+        }
+        swar::clean_prefix_len(token)
+    }
+
+    /// SSE4.2/AVX2 implementation with `is_x86_feature_detected!` runtime dispatch, tested one
+    /// 16- or 32-byte lane at a time.
+    #[cfg(all(target_arch = "x86_64", feature = "std"))]
+    mod x86_64 {
+        use core::arch::x86_64::*;
+
+        #[target_feature(enable = "sse4.2")]
+        unsafe fn in_range_128(data: __m128i, lo: u8, hi: u8) -> __m128i {
+            // Bias by 0x80 so an unsigned byte range test can be done with signed cmpgt.
+            let bias = _mm_set1_epi8(0x80u8 as i8);
+            let biased = _mm_xor_si128(data, bias);
+            let lo_b = _mm_set1_epi8((lo.wrapping_sub(1) ^ 0x80) as i8);
+            let hi_b = _mm_set1_epi8((hi.wrapping_add(1) ^ 0x80) as i8);
+            let ge_lo = _mm_cmpgt_epi8(biased, lo_b);
+            let le_hi = _mm_cmpgt_epi8(hi_b, biased);
+            _mm_and_si128(ge_lo, le_hi)
+        }
+
+        #[target_feature(enable = "sse4.2")]
+        unsafe fn eq_128(data: __m128i, byte: u8) -> __m128i {
+            _mm_cmpeq_epi8(data, _mm_set1_epi8(byte as i8))
+        }
+
+        #[target_feature(enable = "sse4.2")]
+        unsafe fn allowed_mask_128(data: __m128i) -> i32 {
+            let mut allowed = in_range_128(data, b'0', b'9');
+            allowed = _mm_or_si128(allowed, in_range_128(data, b'A', b'Z'));
+            allowed = _mm_or_si128(allowed, in_range_128(data, b'a', b'z'));
+            allowed = _mm_or_si128(allowed, in_range_128(data, b'(', b','));
+            allowed = _mm_or_si128(allowed, in_range_128(data, b'-', b'.'));
+            allowed = _mm_or_si128(allowed, in_range_128(data, b':', b';'));
+            allowed = _mm_or_si128(allowed, in_range_128(data, b'&', b'\''));
+            allowed = _mm_or_si128(allowed, eq_128(data, b'!'));
+            allowed = _mm_or_si128(allowed, eq_128(data, b'$'));
+            allowed = _mm_or_si128(allowed, eq_128(data, b'='));
+            allowed = _mm_or_si128(allowed, eq_128(data, b'@'));
+            allowed = _mm_or_si128(allowed, eq_128(data, b'_'));
+            allowed = _mm_or_si128(allowed, eq_128(data, b'~'));
+            _mm_movemask_epi8(allowed)
+        }
+
+        #[target_feature(enable = "sse4.2")]
+        pub(super) unsafe fn clean_prefix_len_sse42(token: &[u8]) -> usize {
+            const LANES: usize = 16;
+            let mut scanned = 0;
+            for chunk in token.chunks_exact(LANES) {
+                let data = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+                if allowed_mask_128(data) != 0xFFFF {
+                    break;
+                }
+                scanned += LANES;
+            }
+            scanned
+        }
+
+        #[target_feature(enable = "avx2")]
+        unsafe fn in_range_256(data: __m256i, lo: u8, hi: u8) -> __m256i {
+            let bias = _mm256_set1_epi8(0x80u8 as i8);
+            let biased = _mm256_xor_si256(data, bias);
+            let lo_b = _mm256_set1_epi8((lo.wrapping_sub(1) ^ 0x80) as i8);
+            let hi_b = _mm256_set1_epi8((hi.wrapping_add(1) ^ 0x80) as i8);
+            let ge_lo = _mm256_cmpgt_epi8(biased, lo_b);
+            let le_hi = _mm256_cmpgt_epi8(hi_b, biased);
+            _mm256_and_si256(ge_lo, le_hi)
+        }
+
+        #[target_feature(enable = "avx2")]
+        unsafe fn eq_256(data: __m256i, byte: u8) -> __m256i {
+            _mm256_cmpeq_epi8(data, _mm256_set1_epi8(byte as i8))
+        }
+
+        #[target_feature(enable = "avx2")]
+        unsafe fn allowed_mask_256(data: __m256i) -> i32 {
+            let mut allowed = in_range_256(data, b'0', b'9');
+            allowed = _mm256_or_si256(allowed, in_range_256(data, b'A', b'Z'));
+            allowed = _mm256_or_si256(allowed, in_range_256(data, b'a', b'z'));
+            allowed = _mm256_or_si256(allowed, in_range_256(data, b'(', b','));
+            allowed = _mm256_or_si256(allowed, in_range_256(data, b'-', b'.'));
+            allowed = _mm256_or_si256(allowed, in_range_256(data, b':', b';'));
+            allowed = _mm256_or_si256(allowed, in_range_256(data, b'&', b'\''));
+            allowed = _mm256_or_si256(allowed, eq_256(data, b'!'));
+            allowed = _mm256_or_si256(allowed, eq_256(data, b'$'));
+            allowed = _mm256_or_si256(allowed, eq_256(data, b'='));
+            allowed = _mm256_or_si256(allowed, eq_256(data, b'@'));
+            allowed = _mm256_or_si256(allowed, eq_256(data, b'_'));
+            allowed = _mm256_or_si256(allowed, eq_256(data, b'~'));
+            _mm256_movemask_epi8(allowed)
+        }
+
+        #[target_feature(enable = "avx2")]
+        pub(super) unsafe fn clean_prefix_len_avx2(token: &[u8]) -> usize {
+            const LANES: usize = 32;
+            let mut scanned = 0;
+            for chunk in token.chunks_exact(LANES) {
+                let data = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+                if allowed_mask_256(data) != -1 {
+                    break;
                 }
+                scanned += LANES;
             }
-            Err(_) => None,
-        },
+            scanned
+        }
+    }
+
+    /// Portable SWAR (SIMD-within-a-register) fallback for targets other than x86_64 (or when
+    /// neither SSE4.2 nor AVX2 is available at runtime), classifying 8 bytes per `u64` using the
+    /// classic bit-parallel zero/range-finding tricks.
+    mod swar {
+        use core::convert::TryInto;
+
+        const ONES: u64 = 0x0101_0101_0101_0101;
+        const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+        fn pattern(byte: u8) -> u64 {
+            (byte as u64).wrapping_mul(ONES)
+        }
+
+        /// High bit of each lane set where that lane of `word` is zero.
+        fn haszero(word: u64) -> u64 {
+            word.wrapping_sub(ONES) & !word & HIGH_BITS
+        }
+
+        /// High bit of each lane set where that lane of `word` is `< n` (`1 <= n <= 127`).
+        fn hasless(word: u64, n: u8) -> u64 {
+            word.wrapping_sub(pattern(n)) & !word & HIGH_BITS
+        }
+
+        /// High bit of each lane set where that lane of `word` is `> n` (`0 <= n <= 126`).
+        fn hasmore(word: u64, n: u8) -> u64 {
+            (word.wrapping_add(pattern(127 - n)) | word) & HIGH_BITS
+        }
+
+        fn ge(word: u64, lo: u8) -> u64 {
+            HIGH_BITS & !hasless(word, lo)
+        }
+
+        fn le(word: u64, hi: u8) -> u64 {
+            HIGH_BITS & !hasmore(word, hi)
+        }
+
+        fn in_range(word: u64, lo: u8, hi: u8) -> u64 {
+            ge(word, lo) & le(word, hi)
+        }
+
+        fn eq(word: u64, byte: u8) -> u64 {
+            haszero(word ^ pattern(byte))
+        }
+
+        /// High bit of each lane set where that lane of `word` is an allowed, non-`%` byte.
+        fn allowed_mask(word: u64) -> u64 {
+            in_range(word, b'0', b'9')
+                | in_range(word, b'A', b'Z')
+                | in_range(word, b'a', b'z')
+                | in_range(word, b'(', b',')
+                | in_range(word, b'-', b'.')
+                | in_range(word, b':', b';')
+                | in_range(word, b'&', b'\'')
+                | eq(word, b'!')
+                | eq(word, b'$')
+                | eq(word, b'=')
+                | eq(word, b'@')
+                | eq(word, b'_')
+                | eq(word, b'~')
+        }
+
+        pub(crate) fn clean_prefix_len(token: &[u8]) -> usize {
+            const WORD_BYTES: usize = 8;
+            let mut scanned = 0;
+            for chunk in token.chunks_exact(WORD_BYTES) {
+                let word = u64::from_ne_bytes(chunk.try_into().unwrap());
+                if allowed_mask(word) != HIGH_BITS {
+                    break;
+                }
+                scanned += WORD_BYTES;
+            }
+            scanned
+        }
     }
 }
 
-#[cfg(test)]
+/// Checks that every byte of `token` is either unreserved or part of a valid percent-encoded
+/// triplet, returning the first offending byte and its position otherwise.
+fn check_allowed_characters(token: &[u8]) -> Result<(), DecodeError> {
+    #[cfg(feature = "simd")]
+    let mut position = simd::clean_prefix_len(token);
+    #[cfg(not(feature = "simd"))]
+    let mut position = 0;
+    while position < token.len() {
+        let byte = token[position];
+        if byte == b'%' {
+            let has_two_hex_digits = token
+                .get(position + 1..position + 3)
+                .is_some_and(|pair| pair.iter().all(u8::is_ascii_hexdigit));
+            if !has_two_hex_digits {
+                return Err(DecodeError::InvalidPercentEncoding);
+            }
+            position += 3;
+        } else if ALLOWED_CHARACTERS_TABLE[byte as usize] {
+            position += 1;
+        } else {
+            return Err(DecodeError::DisallowedCharacter { byte, position });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
     use test::{black_box, Bencher};
 
+    // A long, JWT/base64url-shaped token with no percent-encoding, used to exercise the bulk
+    // (SIMD/SWAR) fast path in `check_allowed_characters`.
+    const LONG_TOKEN: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    const LONG_URI: &str = concat!("secret-token:", "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789");
+
     fn valid_pairs() -> Vec<(&'static str, &'static str)> {
         vec![
             ("secret-token:s", "s"),
@@ -93,6 +515,7 @@ mod tests {
                 "E92FB7EB-D882-47A4-A265-A0B6135DC842 foo",
             ),
             ("secret-token:%C5%81%C3%B3d%C5%BA", "Łódź"),
+            (LONG_URI, LONG_TOKEN),
         ]
     }
 
@@ -115,6 +538,8 @@ mod tests {
             ":secret-token:",
             ":secret-token:hello",
             "secret-token:%a1",
+            "secret-token:abc%",
+            "secret-token:abc%a",
         ]
     }
 
@@ -131,11 +556,100 @@ mod tests {
     fn test_decode_with_invalid_uris() {
         for input in invalid_uris() {
             println!("Testing {}", input);
-            assert!(decode(input).is_none());
-            assert!(decode(input.as_bytes()).is_none());
+            assert!(decode(input).is_err());
+            assert!(decode(input.as_bytes()).is_err());
         }
     }
 
+    #[test]
+    fn test_decode_returns_specific_errors() {
+        assert_eq!(decode(""), Err(DecodeError::MissingPrefix));
+        assert_eq!(decode("hello"), Err(DecodeError::MissingPrefix));
+        assert_eq!(decode("secret-token:"), Err(DecodeError::EmptyToken));
+        assert_eq!(decode("secret-token:%a1"), Err(DecodeError::InvalidUtf8));
+        assert_eq!(
+            decode("secret-token:%zz"),
+            Err(DecodeError::InvalidPercentEncoding)
+        );
+        assert_eq!(
+            decode("secret-token:%2"),
+            Err(DecodeError::InvalidPercentEncoding)
+        );
+        assert_eq!(
+            decode("secret-token:abc%"),
+            Err(DecodeError::InvalidPercentEncoding)
+        );
+        assert_eq!(
+            decode("secret-token:abc%a"),
+            Err(DecodeError::InvalidPercentEncoding)
+        );
+        assert_eq!(
+            decode("secret-token: "),
+            Err(DecodeError::DisallowedCharacter {
+                byte: b' ',
+                position: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_cow_borrows_when_there_is_no_percent_encoding() {
+        match decode_cow("secret-token:hello") {
+            Ok(Cow::Borrowed(token)) => assert_eq!(token, "hello"),
+            other => panic!("expected a borrowed token, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_cow_allocates_when_percent_encoded() {
+        match decode_cow("secret-token:%C5%81%C3%B3d%C5%BA") {
+            Ok(Cow::Owned(token)) => assert_eq!(token, "Łódź"),
+            other => panic!("expected an owned token, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_cow_matches_decode() {
+        for input in valid_pairs()
+            .into_iter()
+            .map(|(uri, _)| uri)
+            .chain(invalid_uris())
+        {
+            assert_eq!(
+                decode(input),
+                decode_cow(input).map(|token| token.into_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_secret_matches_decode() {
+        for (uri, token) in valid_pairs() {
+            assert_eq!(decode_secret(uri).unwrap(), SecretToken::new(token.into()));
+        }
+        for uri in invalid_uris() {
+            assert_eq!(decode_secret(uri).unwrap_err(), decode(uri).unwrap_err());
+        }
+    }
+
+    #[test]
+    fn test_secret_token_debug_and_display_are_redacted() {
+        let secret = decode_secret("secret-token:hello").unwrap();
+        assert_eq!(format!("{:?}", secret), "secret-token:***");
+        assert_eq!(format!("{}", secret), "secret-token:***");
+    }
+
+    #[test]
+    fn test_secret_token_equality_is_constant_time() {
+        let a = SecretToken::new("hello".into());
+        let b = SecretToken::new("hello".into());
+        let c = SecretToken::new("world".into());
+        let d = SecretToken::new("hello!".into());
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, d);
+    }
+
     #[test]
     fn test_encode() {
         for (input, output) in valid_pairs() {
@@ -159,7 +673,7 @@ mod tests {
                 // Disallowed characters, got percent-encoded here so
                 // it can't exist verbatim in the URIs.
                 println!("Character number {} ({}, encoded {})", i, s, encoded);
-                assert!(decoded.is_none());
+                assert!(decoded.is_err());
             } else {
                 assert_eq!(decoded.unwrap(), std::str::from_utf8(&bytes).unwrap());
             }
@@ -186,6 +700,23 @@ mod tests {
         });
     }
 
+    #[bench]
+    fn bench_decoding_valid_long_token(b: &mut Bencher) {
+        b.iter(|| {
+            black_box(decode(LONG_URI));
+        });
+    }
+
+    #[bench]
+    fn bench_decoding_cow_valid_uris(b: &mut Bencher) {
+        let uris: Vec<&str> = valid_pairs().into_iter().map(|(uri, _)| uri).collect();
+        b.iter(|| {
+            for uri in &uris {
+                black_box(decode_cow(uri));
+            }
+        });
+    }
+
     #[bench]
     fn bench_encoding(b: &mut Bencher) {
         let tokens: Vec<&str> = valid_pairs().into_iter().map(|(_, token)| token).collect();